@@ -0,0 +1,1321 @@
+use tree_sitter::{InputEdit, Language, Node, Parser, Tree, TreeCursor};
+extern "C" {
+    fn tree_sitter_pug() -> Language;
+}
+
+struct Range {
+    html_end: usize,
+    html_start: usize,
+    pug_end: usize,
+    pug_start: usize,
+}
+
+struct State {
+    html_text: String,
+    pug_text: String,
+    ranges: Vec<Range>,
+}
+
+/// The full html span a single top-level node produced, recorded
+/// independently of `Range` so that an unchanged subtree can be reused
+/// wholesale on reparse. Unlike `Range`, this covers the node's *entire*
+/// output, including synthesized wrapper bytes (`<`, `>`, `</tag>`) that
+/// are pushed with `pug_range: None` and therefore never get a `Range`.
+struct NodeSpan {
+    pug_start: usize,
+    pug_end: usize,
+    html_start: usize,
+    html_end: usize,
+}
+
+/// The result of converting a pug source file into its HTML projection.
+///
+/// Alongside the generated `html`, this keeps two indices over the ranges
+/// recorded during conversion (one ordered by html offset, one by pug
+/// offset) so that `html_offset_for_pug`/`pug_offset_for_html` can answer
+/// queries with a binary search instead of a linear scan.
+pub struct ConversionResult {
+    html: String,
+    pug: String,
+    ranges_by_html: Vec<Range>,
+    ranges_by_pug: Vec<Range>,
+    top_level_spans: Vec<NodeSpan>,
+}
+
+impl ConversionResult {
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+
+    pub fn pug(&self) -> &str {
+        &self.pug
+    }
+
+    /// Translates a byte offset in the pug source into the corresponding
+    /// byte offset in the generated HTML, interpolating within the
+    /// covering range and snapping to the nearest range when the offset
+    /// falls in pug syntax that has no html counterpart (e.g. the `(`/`)`
+    /// around attributes, or the whitespace between a tag and its content).
+    pub fn html_offset_for_pug(&self, pug_byte: usize) -> Option<usize> {
+        if pug_byte > self.pug.len() {
+            return None;
+        }
+        let range = find_covering_range(&self.ranges_by_pug, pug_byte, |r| r.pug_start, |r| r.pug_end)?;
+        Some(interpolate(
+            pug_byte,
+            range.pug_start,
+            range.pug_end,
+            range.html_start,
+            range.html_end,
+        ))
+    }
+
+    /// Translates a byte offset in the generated HTML into the
+    /// corresponding byte offset in the pug source. See
+    /// `html_offset_for_pug` for the interpolation/snapping behaviour; here
+    /// the unmapped bytes are synthesized literals such as `<`, `>`, `</tag>`.
+    pub fn pug_offset_for_html(&self, html_byte: usize) -> Option<usize> {
+        if html_byte > self.html.len() {
+            return None;
+        }
+        let range = find_covering_range(&self.ranges_by_html, html_byte, |r| r.html_start, |r| r.html_end)?;
+        Some(interpolate(
+            html_byte,
+            range.html_start,
+            range.html_end,
+            range.pug_start,
+            range.pug_end,
+        ))
+    }
+}
+
+/// Finds the range that covers `byte`, or the nearest range to it if `byte`
+/// falls in a gap between two ranges (an unmapped literal the converter
+/// emitted, such as a synthesized `<` or `</tag>`).
+fn find_covering_range(
+    sorted_ranges: &[Range],
+    byte: usize,
+    start_of: impl Fn(&Range) -> usize,
+    end_of: impl Fn(&Range) -> usize,
+) -> Option<&Range> {
+    if sorted_ranges.is_empty() {
+        return None;
+    }
+
+    let idx = sorted_ranges.partition_point(|range| end_of(range) <= byte);
+
+    if idx >= sorted_ranges.len() {
+        return sorted_ranges.last();
+    }
+
+    let candidate = &sorted_ranges[idx];
+    if start_of(candidate) <= byte || idx == 0 {
+        return Some(candidate);
+    }
+
+    let previous = &sorted_ranges[idx - 1];
+    if byte - end_of(previous) <= start_of(candidate) - byte {
+        Some(previous)
+    } else {
+        Some(candidate)
+    }
+}
+
+/// Maps `byte` from `[src_start, src_end)` into `[dst_start, dst_end)`,
+/// clamping to the destination bounds and interpolating proportionally
+/// in between.
+fn interpolate(byte: usize, src_start: usize, src_end: usize, dst_start: usize, dst_end: usize) -> usize {
+    if byte <= src_start {
+        return dst_start;
+    }
+    if byte >= src_end {
+        return dst_end;
+    }
+
+    let src_len = src_end - src_start;
+    let dst_len = dst_end - dst_start;
+    if src_len == 0 {
+        return dst_start;
+    }
+
+    dst_start + (byte - src_start) * dst_len / src_len
+}
+
+/// Escapes text content the way a browser-grade HTML serializer would:
+/// `&`, `<` and `>` are the only characters that can reintroduce markup.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Picks the quote character to wrap `inner` in: double quotes unless the
+/// value contains a `"` but no `'`. A value containing both kinds of quote
+/// is wrapped in double quotes, whichever kind occurs more often.
+fn choose_attribute_quote(inner: &str) -> char {
+    if inner.contains('"') && !inner.contains('\'') {
+        '\''
+    } else {
+        '"'
+    }
+}
+
+/// Escapes an attribute value for serialization inside `quote`: `&`, `<`
+/// and whichever quote character is being used to wrap the value.
+fn escape_attribute_value(inner: &str, quote: char) -> String {
+    let mut escaped = String::with_capacity(inner.len());
+    for c in inner.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' if quote == '"' => escaped.push_str("&quot;"),
+            '\'' if quote == '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Neutralizes `--` runs (and a trailing `-` that would otherwise combine
+/// with the synthesized `-->`) in a pug comment's body by inserting a
+/// space between consecutive hyphens, so a comment containing `-->`
+/// cannot prematurely close the generated HTML comment.
+fn escape_comment_body(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '-' && escaped.ends_with('-') {
+            escaped.push(' ');
+        }
+        escaped.push(c);
+    }
+    if escaped.ends_with('-') {
+        escaped.push(' ');
+    }
+    escaped
+}
+
+/// Strips a single matching pair of surrounding quote characters, if
+/// present, from a `quoted_attribute_value` token.
+fn strip_quotes(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    if bytes.len() >= 2 && (bytes[0] == b'"' || bytes[0] == b'\'') && bytes[bytes.len() - 1] == bytes[0] {
+        &text[1..text.len() - 1]
+    } else {
+        text
+    }
+}
+
+/// Builds a synthetic `tree_sitter::Range` for a byte span we've computed
+/// ourselves (e.g. a `{{ }}` expression found by scanning raw text rather
+/// than by the grammar). Only `start_byte`/`end_byte` are ever read back
+/// out of a range by `push_range`, so the points are left at the origin.
+fn byte_range(start_byte: usize, end_byte: usize) -> tree_sitter::Range {
+    tree_sitter::Range {
+        start_byte,
+        end_byte,
+        start_point: tree_sitter::Point { row: 0, column: 0 },
+        end_point: tree_sitter::Point { row: 0, column: 0 },
+    }
+}
+
+/// Scans `text` (which starts at pug byte `base_start`) for `{{ ... }}`
+/// mustache/Angular interpolations, emitting the surrounding literal text
+/// (escaped via `escape`) as ordinary ranges and each expression as a
+/// `<script>return ...;</script>` probe, matching how
+/// `escaped_string_interpolation` already surfaces JavaScript expressions.
+/// `\{{` is treated as an escaped, literal `{{`.
+fn push_text_with_interpolations(
+    state: &mut State,
+    text: &str,
+    base_start: usize,
+    escape: impl Fn(&str) -> String,
+) {
+    let bytes = text.as_bytes();
+    let len = bytes.len();
+    let mut i = 0;
+    let mut literal_start = 0;
+
+    while i < len {
+        if text[i..].starts_with("\\{{") {
+            flush_literal(state, text, literal_start, i, base_start, &escape);
+            push_range(
+                state,
+                "{{",
+                Some(byte_range(base_start + i + 1, base_start + i + 3)),
+            );
+            i += 3;
+            literal_start = i;
+            continue;
+        }
+
+        if text[i..].starts_with("{{") {
+            if let Some(close) = find_closing_mustache(bytes, i + 2) {
+                flush_literal(state, text, literal_start, i, base_start, &escape);
+
+                let raw_expr = &text[i + 2..close];
+                let trimmed = raw_expr.trim();
+                let trim_start = i + 2 + (raw_expr.len() - raw_expr.trim_start().len());
+                let trim_end = trim_start + trimmed.len();
+
+                push_range(state, "<script>return ", None);
+                push_range(
+                    state,
+                    trimmed,
+                    Some(byte_range(base_start + trim_start, base_start + trim_end)),
+                );
+                push_range(state, ";</script>", None);
+
+                i = close + 2;
+                literal_start = i;
+                continue;
+            }
+        }
+
+        let char_len = text[i..].chars().next().map_or(1, |c| c.len_utf8());
+        i += char_len;
+    }
+
+    flush_literal(state, text, literal_start, len, base_start, &escape);
+}
+
+fn flush_literal(
+    state: &mut State,
+    text: &str,
+    start: usize,
+    end: usize,
+    base_start: usize,
+    escape: impl Fn(&str) -> String,
+) {
+    if start >= end {
+        return;
+    }
+
+    let slice = &text[start..end];
+    push_range(
+        state,
+        &escape(slice),
+        Some(byte_range(base_start + start, base_start + end)),
+    );
+}
+
+/// Finds the byte index of the first `}` of the closing `}}` for a
+/// mustache expression that opened at `start` (just past the opening
+/// `{{`), tracking brace depth so nested object literals like
+/// `{{ {a: 1} }}` don't terminate early. Returns `None` when the `{{` is
+/// unterminated, in which case it is left as literal text.
+fn find_closing_mustache(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    let mut depth = 0i32;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' if depth > 0 => {
+                depth -= 1;
+                i += 1;
+            }
+            b'}' if i + 1 < bytes.len() && bytes[i + 1] == b'}' => {
+                return Some(i);
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+
+    None
+}
+
+fn is_void_element(tag_name: &str) -> bool {
+    match tag_name {
+        "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta"
+        | "param" | "source" | "track" | "wbr" => {
+            return true;
+        }
+        _ => {
+            return false;
+        }
+    }
+}
+
+/// Converts pug source into its HTML projection, returning both the
+/// generated HTML and a bidirectional locator between pug and HTML byte
+/// offsets. This is the one-shot entry point; an editor/LSP that reparses
+/// on every keystroke should use `Locator` instead so unchanged subtrees
+/// don't get retraversed from scratch.
+pub fn convert(pug: &str) -> ConversionResult {
+    let mut parser = Parser::new();
+
+    let language = unsafe { tree_sitter_pug() };
+    parser.set_language(language).unwrap();
+
+    let tree = parser.parse(pug, None).unwrap();
+    convert_tree(&tree, pug, None)
+}
+
+/// Retains the previous `Tree` and `ConversionResult` across edits so an
+/// editor/LSP can feed incremental edits to tree-sitter instead of
+/// reparsing and reconverting the whole document on every keystroke.
+pub struct Locator {
+    parser: Parser,
+    tree: Tree,
+    result: ConversionResult,
+}
+
+impl Locator {
+    pub fn new(pug: &str) -> Locator {
+        let mut parser = Parser::new();
+
+        let language = unsafe { tree_sitter_pug() };
+        parser.set_language(language).unwrap();
+
+        let tree = parser.parse(pug, None).unwrap();
+        let result = convert_tree(&tree, pug, None);
+
+        Locator {
+            parser,
+            tree,
+            result,
+        }
+    }
+
+    pub fn result(&self) -> &ConversionResult {
+        &self.result
+    }
+
+    /// Applies a tree-sitter `InputEdit` and reconverts `new_src`. Subtrees
+    /// tree-sitter reports as unaffected by the edit reuse their previously
+    /// computed html/ranges instead of being retraversed.
+    ///
+    /// `has_changes()` only carries meaningful information on the *old*
+    /// tree's nodes, read right after `Tree::edit` shifts their byte ranges
+    /// but before the tree is reparsed — once a fresh tree comes back from
+    /// `Parser::parse`, every one of its nodes reports `has_changes() ==
+    /// false`, since that tree was never edited itself. So the unchanged
+    /// top-level spans are snapshotted from the old tree first, in the
+    /// post-edit byte coordinates `Tree::edit` leaves it in, and matched
+    /// against the new tree's top-level children by byte range.
+    pub fn reparse(&mut self, edit: InputEdit, new_src: &str) -> &ConversionResult {
+        self.tree.edit(&edit);
+
+        let unchanged_spans: Vec<(usize, usize)> = {
+            let mut old_cursor = self.tree.walk();
+            let old_root = self.tree.root_node();
+            if old_root.is_named() {
+                old_root
+                    .named_children(&mut old_cursor)
+                    .filter(|child| !child.has_changes())
+                    .map(|child| (child.start_byte(), child.end_byte()))
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        };
+
+        self.tree = self.parser.parse(new_src, Some(&self.tree)).unwrap();
+        self.result = convert_tree(
+            &self.tree,
+            new_src,
+            Some((&self.result, &edit, &unchanged_spans)),
+        );
+
+        &self.result
+    }
+}
+
+/// The previous conversion to diff against, the edit that was applied to
+/// it, and the pre-reparse snapshot of which of the old tree's top-level
+/// nodes `has_changes()` reported as untouched (by post-edit byte range).
+type PreviousConversion<'a> = (&'a ConversionResult, &'a InputEdit, &'a [(usize, usize)]);
+
+fn convert_tree(tree: &Tree, source: &str, previous: Option<PreviousConversion>) -> ConversionResult {
+    let mut root_node = tree.root_node();
+    let source_bytes = source.as_bytes();
+
+    let mut state = State {
+        html_text: String::new(),
+        pug_text: source.to_string(),
+        ranges: Vec::new(),
+    };
+    let mut top_level_spans = Vec::new();
+
+    if root_node.is_named() {
+        let mut cursor = root_node.walk();
+        for mut child in root_node.named_children(&mut cursor) {
+            let html_start = state.html_text.len();
+
+            let reused = match previous {
+                Some((previous_result, edit, unchanged_spans))
+                    if unchanged_spans.contains(&(child.start_byte(), child.end_byte())) =>
+                {
+                    reuse_cached_span(&child, previous_result, edit, &mut state)
+                }
+                _ => false,
+            };
+            if !reused {
+                traverse_tree(&mut child, source_bytes, &mut state);
+            }
+
+            top_level_spans.push(NodeSpan {
+                pug_start: child.start_byte(),
+                pug_end: child.end_byte(),
+                html_start,
+                html_end: state.html_text.len(),
+            });
+        }
+    } else {
+        traverse_tree(&mut root_node, source_bytes, &mut state);
+    }
+
+    build_conversion_result(state, top_level_spans)
+}
+
+/// Reuses the html a previous conversion already produced for `node`'s
+/// *entire* pug span (looked up by exact pre-edit byte range in
+/// `previous.top_level_spans`, not inferred from individual `Range`
+/// entries, since synthesized wrapper bytes like `<`, `>` and `</tag>`
+/// are pushed with `pug_range: None` and never get one) instead of
+/// retraversing it. Also shifts any `Range`s previously recorded inside
+/// that span so locator queries keep working.
+fn reuse_cached_span(
+    node: &Node,
+    previous: &ConversionResult,
+    edit: &InputEdit,
+    state: &mut State,
+) -> bool {
+    let Some(old_start) = byte_before_edit(node.start_byte(), edit) else {
+        return false;
+    };
+    let Some(old_end) = byte_before_edit(node.end_byte(), edit) else {
+        return false;
+    };
+
+    let Some(span) = previous
+        .top_level_spans
+        .iter()
+        .find(|span| span.pug_start == old_start && span.pug_end == old_end)
+    else {
+        return false;
+    };
+
+    let html_offset = state.html_text.len();
+    let pug_shift = node.start_byte() as i64 - old_start as i64;
+
+    state.html_text.push_str(&previous.html[span.html_start..span.html_end]);
+
+    let first = previous
+        .ranges_by_pug
+        .partition_point(|range| range.pug_start < old_start);
+    let mut last = first;
+    while last < previous.ranges_by_pug.len() && previous.ranges_by_pug[last].pug_end <= old_end {
+        last += 1;
+    }
+
+    for range in &previous.ranges_by_pug[first..last] {
+        state.ranges.push(Range {
+            html_start: html_offset + (range.html_start - span.html_start),
+            html_end: html_offset + (range.html_end - span.html_start),
+            pug_start: (range.pug_start as i64 + pug_shift) as usize,
+            pug_end: (range.pug_end as i64 + pug_shift) as usize,
+        });
+    }
+
+    true
+}
+
+/// Maps a post-edit byte offset back to its pre-edit equivalent, for
+/// offsets entirely before or entirely after a single contiguous edit.
+/// Returns `None` for offsets inside the edited region itself.
+fn byte_before_edit(new_byte: usize, edit: &InputEdit) -> Option<usize> {
+    if new_byte <= edit.start_byte {
+        Some(new_byte)
+    } else if new_byte >= edit.new_end_byte {
+        let delta = edit.new_end_byte as i64 - edit.old_end_byte as i64;
+        Some((new_byte as i64 - delta) as usize)
+    } else {
+        None
+    }
+}
+
+fn build_conversion_result(state: State, top_level_spans: Vec<NodeSpan>) -> ConversionResult {
+    let mut ranges_by_pug = Vec::with_capacity(state.ranges.len());
+    let mut ranges_by_html = Vec::with_capacity(state.ranges.len());
+    for range in &state.ranges {
+        ranges_by_pug.push(Range {
+            html_start: range.html_start,
+            html_end: range.html_end,
+            pug_start: range.pug_start,
+            pug_end: range.pug_end,
+        });
+        ranges_by_html.push(Range {
+            html_start: range.html_start,
+            html_end: range.html_end,
+            pug_start: range.pug_start,
+            pug_end: range.pug_end,
+        });
+    }
+    ranges_by_pug.sort_by_key(|range| range.pug_start);
+    ranges_by_html.sort_by_key(|range| range.html_start);
+
+    ConversionResult {
+        html: state.html_text,
+        pug: state.pug_text,
+        ranges_by_html,
+        ranges_by_pug,
+        top_level_spans,
+    }
+}
+
+fn push_range(state: &mut State, to_push: &str, pug_range: Option<tree_sitter::Range>) {
+    match pug_range {
+        Some(range) => {
+            let html_len = state.html_text.len();
+
+            let range = Range {
+                html_start: html_len,
+                html_end: html_len + to_push.len(),
+                pug_start: range.start_byte,
+                pug_end: range.end_byte,
+            };
+
+            state.ranges.push(range);
+        }
+        _ => {}
+    }
+
+    state.html_text.push_str(&to_push);
+}
+
+/// Whether `node` (a `tag`) carries an `attributes` group with an explicit
+/// `class="..."` entry that the `.foo` shorthand classes should be merged
+/// into, rather than emitted as their own separate `class` attribute.
+fn tag_has_mergeable_class_attribute(node: &Node, source: &[u8]) -> bool {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        if child.kind() != "attributes" {
+            continue;
+        }
+
+        let mut attribute_cursor = child.walk();
+        for attribute in child.named_children(&mut attribute_cursor) {
+            let mut name_cursor = attribute.walk();
+            let Some(attribute_name) = attribute.named_children(&mut name_cursor).next() else {
+                continue;
+            };
+            if attribute_name.utf8_text(source).unwrap() == "class" {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn visit_attributes(
+    cursor: &mut TreeCursor,
+    node: &mut Node,
+    source: &[u8],
+    state: &mut State,
+    shorthand_classes: &[Node],
+) {
+    let mut first = true;
+
+    let mut child_cursor = cursor.clone();
+    for attribute in node.named_children(&mut child_cursor) {
+        if !first {
+            push_range(state, ", ", None);
+        } else {
+            first = false;
+        }
+
+        let mut attribute_cursor = cursor.clone();
+        let mut children = attribute.named_children(&mut attribute_cursor);
+
+        let attribute_name = children.next().unwrap();
+        let attribute_value = children.next();
+
+        let name_text = attribute_name.utf8_text(source).unwrap();
+        let is_class_attribute = name_text == "class";
+        push_range(state, name_text, Some(attribute_name.range()));
+        push_range(state, "=", None);
+
+        match attribute_value {
+            Some(attribute_value) => {
+                let text = attribute_value.utf8_text(source).unwrap().to_string();
+
+                match attribute_value.kind() {
+                    // Just make javascript attributes into valid HTML
+                    "javascript" => {
+                        if is_class_attribute && !shorthand_classes.is_empty() {
+                            push_range(state, "'", None);
+                            for class_node in shorthand_classes {
+                                push_range(
+                                    state,
+                                    &shorthand_token(class_node, source),
+                                    Some(shorthand_token_range(class_node, source)),
+                                );
+                                push_range(state, " ", None);
+                            }
+                            push_range(state, "' + (", None);
+                            push_range(state, &text, Some(attribute_value.range()));
+                            push_range(state, ")", None);
+                        } else {
+                            push_range_surround(state, &text, attribute_value.range(), "'");
+                        }
+                    }
+                    "quoted_attribute_value" => {
+                        let inner = strip_quotes(&text);
+                        let quote = choose_attribute_quote(inner);
+                        let value_range = attribute_value.range();
+                        let inner_start = value_range.start_byte + 1;
+
+                        push_range(
+                            state,
+                            &quote.to_string(),
+                            Some(byte_range(value_range.start_byte, inner_start)),
+                        );
+                        if is_class_attribute && !shorthand_classes.is_empty() {
+                            for class_node in shorthand_classes {
+                                push_range(
+                                    state,
+                                    &shorthand_token(class_node, source),
+                                    Some(shorthand_token_range(class_node, source)),
+                                );
+                                push_range(state, " ", None);
+                            }
+                        }
+                        push_text_with_interpolations(state, inner, inner_start, |s| {
+                            escape_attribute_value(s, quote)
+                        });
+                        push_range(
+                            state,
+                            &quote.to_string(),
+                            Some(byte_range(value_range.end_byte - 1, value_range.end_byte)),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            None => {
+                push_range_surround(
+                    state,
+                    attribute_name.utf8_text(source).unwrap(),
+                    attribute_name.range(),
+                    "'",
+                );
+            }
+        }
+    }
+}
+
+/// Decomposes `tag.foo.bar` class shorthand segments into a single
+/// `class="foo bar"` attribute, keeping each class token's own range
+/// pointing back at its originating `.foo` pug byte span, the same way a
+/// CSS selector decomposes into its constituent class segments.
+fn push_class_shorthand(state: &mut State, classes: &[Node], source: &[u8]) {
+    if classes.is_empty() {
+        return;
+    }
+
+    push_range(state, " class=\"", None);
+    for (index, class_node) in classes.iter().enumerate() {
+        if index > 0 {
+            push_range(state, " ", None);
+        }
+        push_range(state, &shorthand_token(class_node, source), Some(shorthand_token_range(class_node, source)));
+    }
+    push_range(state, "\"", None);
+}
+
+/// Turns a `tag#id` shorthand segment into an `id="..."` attribute, with
+/// the generated value mapped back to just the `id` token (not the `#`).
+fn push_id_shorthand(state: &mut State, id: Option<Node>, source: &[u8]) {
+    let Some(id_node) = id else {
+        return;
+    };
+
+    push_range(state, " id=\"", None);
+    push_range(state, &shorthand_token(&id_node, source), Some(shorthand_token_range(&id_node, source)));
+    push_range(state, "\"", None);
+}
+
+/// The `.`/`#` shorthand text, with the leading selector punctuation
+/// stripped.
+fn shorthand_token(node: &Node, source: &[u8]) -> String {
+    node.utf8_text(source)
+        .unwrap()
+        .trim_start_matches(['.', '#'])
+        .to_string()
+}
+
+/// The pug byte range of just the shorthand token, excluding its leading
+/// `.`/`#`.
+fn shorthand_token_range(node: &Node, source: &[u8]) -> tree_sitter::Range {
+    let text = node.utf8_text(source).unwrap();
+    let token = text.trim_start_matches(['.', '#']);
+    let token_start = node.start_byte() + (text.len() - token.len());
+    byte_range(token_start, node.end_byte())
+}
+
+fn push_range_surround(
+    state: &mut State,
+    to_push: &str,
+    pug_range: tree_sitter::Range,
+    surround: &str,
+) {
+    push_range(state, surround, None);
+    push_range(state, to_push, Some(pug_range));
+    push_range(state, surround, None);
+}
+
+fn visit_tag(cursor: &mut TreeCursor, node: &mut Node, source: &[u8], state: &mut State) {
+    let mut cursor_mutable = cursor.clone();
+
+    let mut child_nodes = node.named_children(&mut cursor_mutable).peekable();
+    let name_node = child_nodes.next().unwrap();
+    let name = name_node.utf8_text(source).unwrap();
+
+    push_range(state, "<", None);
+    push_range(state, name, Some(name_node.range()));
+
+    let mut classes = Vec::new();
+    let mut id = None;
+
+    while let Some(peeked) = child_nodes.peek() {
+        match peeked.kind() {
+            "class" => classes.push(child_nodes.next().unwrap()),
+            "id" => id = child_nodes.next(),
+            _ => break,
+        }
+    }
+
+    if !tag_has_mergeable_class_attribute(node, source) {
+        push_class_shorthand(state, &classes, source);
+    }
+    push_id_shorthand(state, id, source);
+
+    let mut has_closed_open_tag = false;
+
+    for mut child_node in child_nodes {
+        if child_node.kind() == "attributes" {
+            push_range(state, " ", None);
+            let mut attributes_cursor = child_node.walk();
+            visit_attributes(&mut attributes_cursor, &mut child_node, source, state, &classes);
+            continue;
+        }
+
+        if child_node.kind() == "mixin_attributes" {
+            push_range(state, " ", None);
+            traverse_tree(&mut child_node, source, state);
+            continue;
+        }
+
+        if is_void_element(name) {
+            push_range(state, "/>", None);
+            break;
+        }
+
+        if !has_closed_open_tag {
+            push_range(state, ">", None);
+            has_closed_open_tag = true;
+        }
+
+        if child_node.kind() == "content" {
+            traverse_tree(&mut child_node, source, state);
+            continue;
+        }
+
+        if child_node.kind() == "children" {
+            traverse_tree(&mut child_node, source, state);
+            continue;
+        }
+    }
+
+    if !has_closed_open_tag {
+        push_range(state, ">", None);
+    }
+
+    if !is_void_element(name) {
+        push_range(state, &format!("</{}>", name).to_string(), None);
+    }
+}
+
+fn visit_conditional(cursor: &mut TreeCursor, node: &mut Node, source: &[u8], state: &mut State) {
+    let mut child_cursor = cursor.clone();
+    let mut conditional_cursor = node.walk();
+
+    conditional_cursor.goto_first_child();
+    conditional_cursor.goto_next_sibling();
+
+    if conditional_cursor.node().kind() == "javascript" {
+        let condition = conditional_cursor.node();
+
+        push_range(state, "<script>return ", None);
+        push_range(
+            state,
+            condition.utf8_text(source).unwrap(),
+            Some(condition.range()),
+        );
+        push_range(state, ";</script>", None);
+        conditional_cursor.goto_next_sibling();
+    }
+
+    conditional_cursor.goto_next_sibling();
+
+    let children = conditional_cursor.node().named_children(&mut child_cursor);
+    for mut child in children {
+        traverse_tree(&mut child, source, state);
+    }
+}
+
+/// Handles `each`/`while` iteration: emits the loop's javascript
+/// expression (the iterable for `each`, the condition for `while`) as a
+/// scripted probe, then recurses into the body. Named children are
+/// scanned by `kind()` rather than position, since `each value, key in
+/// items` binds one or two loop variables between the keyword and the
+/// expression, which would otherwise shift where the expression and body
+/// fall positionally (the same reason `visit_case`/`visit_case_branch`
+/// scan by kind instead).
+fn visit_iteration(cursor: &mut TreeCursor, node: &mut Node, source: &[u8], state: &mut State) {
+    let mut child_cursor = cursor.clone();
+
+    for mut child in node.named_children(&mut child_cursor) {
+        match child.kind() {
+            "javascript" => {
+                push_range(state, "<script>return ", None);
+                push_range(
+                    state,
+                    child.utf8_text(source).unwrap(),
+                    Some(child.range()),
+                );
+                push_range(state, ";</script>", None);
+            }
+            "children" => traverse_tree(&mut child, source, state),
+            _ => {}
+        }
+    }
+}
+
+/// Handles `case`/`when`/`default`: emits the selector expression as a
+/// scripted probe, then recurses into every branch so nested content
+/// under each `when`/`default` keeps a source mapping.
+fn visit_case(cursor: &mut TreeCursor, node: &mut Node, source: &[u8], state: &mut State) {
+    let mut branch_cursor = cursor.clone();
+    let mut case_cursor = node.walk();
+
+    case_cursor.goto_first_child();
+    case_cursor.goto_next_sibling();
+
+    if case_cursor.node().kind() == "javascript" {
+        let selector = case_cursor.node();
+
+        push_range(state, "<script>return ", None);
+        push_range(
+            state,
+            selector.utf8_text(source).unwrap(),
+            Some(selector.range()),
+        );
+        push_range(state, ";</script>", None);
+    }
+
+    for mut branch in node.named_children(&mut branch_cursor) {
+        if branch.kind() == "when" || branch.kind() == "default" {
+            visit_case_branch(&mut branch, source, state);
+        }
+    }
+}
+
+/// Recurses into a single `when`/`default` branch, emitting its match
+/// expression (if any) as a scripted probe before traversing its body.
+fn visit_case_branch(node: &mut Node, source: &[u8], state: &mut State) {
+    let mut cursor = node.walk();
+
+    for mut child in node.named_children(&mut cursor) {
+        if child.kind() == "javascript" {
+            push_range(state, "<script>return ", None);
+            push_range(state, child.utf8_text(source).unwrap(), Some(child.range()));
+            push_range(state, ";</script>", None);
+            continue;
+        }
+
+        traverse_tree(&mut child, source, state);
+    }
+}
+
+fn visit_pipe(cursor: &mut TreeCursor, _node: &mut Node, source: &[u8], state: &mut State) {
+    cursor.goto_first_child();
+    while cursor.goto_next_sibling() {
+        if cursor.node().is_named() {
+            traverse_tree(&mut cursor.node(), source, state);
+        }
+    }
+}
+
+/// Surfaces a pug `&attributes(obj)` spread as a scripted probe (the same
+/// `<script>return ...;</script>` wrapper used for other JavaScript
+/// expressions) rather than silently dropping it.
+fn visit_mixin_attributes(node: &mut Node, source: &[u8], state: &mut State) {
+    let mut cursor = node.walk();
+    let expression = node.named_children(&mut cursor).next();
+    if let Some(expression) = expression {
+        push_range(state, "<script>return ", None);
+        push_range(
+            state,
+            expression.utf8_text(source).unwrap(),
+            Some(expression.range()),
+        );
+        push_range(state, ";</script>", None);
+    }
+}
+
+/// Emits a pug comment as an HTML comment, distinguishing buffered `//`
+/// (rendered) from unbuffered `//-` (dropped, consuming source but
+/// contributing nothing to the HTML projection).
+fn visit_comment(node: &mut Node, source: &[u8], state: &mut State) {
+    let text = node.utf8_text(source).unwrap();
+    if text.starts_with("//-") {
+        return;
+    }
+
+    let body = &text[2..];
+    let trimmed = body.trim();
+    let trim_start_offset = body.len() - body.trim_start().len();
+    let content_start = node.start_byte() + 2 + trim_start_offset;
+    let content_end = content_start + trimmed.len();
+
+    push_range(state, "<!--", None);
+    push_range(
+        state,
+        &escape_comment_body(trimmed),
+        Some(byte_range(content_start, content_end)),
+    );
+    push_range(state, "-->", None);
+}
+
+fn visit_tag_interpolation(
+    _cursor: &mut TreeCursor,
+    node: &mut Node,
+    source: &[u8],
+    state: &mut State,
+) {
+    let mut interpolation_cursor = node.walk();
+
+    interpolation_cursor.goto_first_child();
+    interpolation_cursor.goto_next_sibling();
+    let children = interpolation_cursor
+        .node()
+        .named_children(&mut interpolation_cursor);
+
+    for mut child in children {
+        traverse_tree(&mut child, source, state);
+    }
+}
+
+fn traverse_tree(node: &mut Node, source: &[u8], state: &mut State) {
+    let node_type = node.kind();
+
+    let mut cursor = node.walk();
+
+    if node.is_named() {
+        match node_type {
+            "source_file" | "children" => {
+                let mut child_cursor = cursor.clone();
+                let children = node.named_children(&mut child_cursor);
+                for mut child in children {
+                    traverse_tree(&mut child, source, state);
+                }
+            }
+            "escaped_string_interpolation" => {
+                let interpolation_content = node.named_children(&mut cursor).next();
+                match interpolation_content {
+                    Some(interpolation_content) => {
+                        let text = interpolation_content.utf8_text(source).unwrap();
+                        push_range(state, "<script>return ", None);
+                        push_range(state, text, Some(interpolation_content.range()));
+                        push_range(state, ";</script>", None);
+                    }
+                    None => {}
+                }
+            }
+            "tag_interpolation" => {
+                visit_tag_interpolation(&mut cursor, node, source, state);
+            }
+            "pipe" => {
+                visit_pipe(&mut cursor, node, source, state);
+            }
+            "conditional" => {
+                visit_conditional(&mut cursor, node, source, state);
+            }
+            "iteration" => {
+                visit_iteration(&mut cursor, node, source, state);
+            }
+            "case" => {
+                visit_case(&mut cursor, node, source, state);
+            }
+            "tag" => visit_tag(&mut cursor, node, source, state),
+            "attributes" => visit_attributes(&mut cursor, node, source, state, &[]),
+            "mixin_attributes" => visit_mixin_attributes(node, source, state),
+            "content" => {
+                // Named children (e.g. `#{...}` interpolations) are interleaved
+                // with literal text, so the literal portions between them are
+                // scanned (and only those), instead of re-scanning the whole
+                // node's text after traversing its children, which would
+                // duplicate whatever the children already emitted.
+                let mut literal_start = node.start_byte();
+                for mut interpolation in node.named_children(&mut cursor) {
+                    let literal =
+                        std::str::from_utf8(&source[literal_start..interpolation.start_byte()])
+                            .unwrap();
+                    push_text_with_interpolations(state, literal, literal_start, escape_text);
+
+                    traverse_tree(&mut interpolation, source, state);
+                    literal_start = interpolation.end_byte();
+                }
+                let literal =
+                    std::str::from_utf8(&source[literal_start..node.end_byte()]).unwrap();
+                push_text_with_interpolations(state, literal, literal_start, escape_text);
+            }
+            "comment" => visit_comment(node, source, state),
+            "keyword" => {}
+            _ => {
+                println!("Unhandled node type: {}", node_type);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_offset_for_pug_round_trips_through_a_tag() {
+        let result = convert("p hello");
+        let pug_byte = "p hello".find("hello").unwrap();
+        let html_byte = result.html_offset_for_pug(pug_byte).unwrap();
+        assert_eq!(&result.html()[html_byte..html_byte + 5], "hello");
+    }
+
+    #[test]
+    fn pug_offset_for_html_snaps_to_nearest_range_in_synthesized_literals() {
+        let result = convert("p hello");
+        // Offset 0 in the html is the synthesized '<', which has no range of
+        // its own; it should snap to the nearest mapped range rather than
+        // returning None.
+        assert!(result.pug_offset_for_html(0).is_some());
+    }
+
+    #[test]
+    fn escapes_text_content() {
+        let result = convert(r#"p Hello <world> & "you""#);
+        assert!(result.html().contains("Hello &lt;world&gt; &amp; \"you\""));
+    }
+
+    #[test]
+    fn escaped_content_still_resolves_locator_queries() {
+        let pug = r#"p Hello <world> & "you""#;
+        let result = convert(pug);
+        let pug_byte = pug.find("world").unwrap();
+        let html_byte = result.html_offset_for_pug(pug_byte).unwrap();
+        assert!(result.html()[html_byte..].starts_with("world"));
+    }
+
+    #[test]
+    fn escapes_attribute_values_and_picks_a_quote_that_avoids_escaping() {
+        let result = convert(r#"p(title="a & b")"#);
+        assert!(result.html().contains(r#"title="a &amp; b""#));
+
+        let result = convert(r#"p(title='has "quotes"')"#);
+        assert!(result.html().contains(r#"title='has "quotes"'"#));
+    }
+
+    #[test]
+    fn emits_angular_interpolations_in_content_as_script_probes() {
+        let result = convert("p Hello {{ name }}!");
+        assert!(result.html().contains("Hello "));
+        assert!(result.html().contains("<script>return name;</script>"));
+        assert!(result.html().contains("!"));
+    }
+
+    #[test]
+    fn does_not_duplicate_literal_text_alongside_escaped_string_interpolations() {
+        let result = convert("p Hello #{name}");
+        let occurrences = result.html().matches("Hello ").count();
+        assert_eq!(occurrences, 1);
+        assert!(result.html().contains("<script>return name;</script>"));
+    }
+
+    #[test]
+    fn emits_angular_interpolations_in_attribute_values() {
+        let result = convert(r#"p(title="{{ name }}")"#);
+        assert!(result.html().contains(r#"title="<script>return name;</script>""#));
+    }
+
+    #[test]
+    fn handles_nested_braces_in_object_literal_interpolations() {
+        let result = convert("p {{ fn({a: 1}) }}");
+        assert!(result.html().contains("<script>return fn({a: 1});</script>"));
+    }
+
+    #[test]
+    fn treats_unterminated_interpolation_as_literal_text() {
+        let result = convert("p Hello {{ world");
+        assert!(result.html().contains("Hello {{ world"));
+    }
+
+    #[test]
+    fn treats_escaped_interpolation_as_literal_braces() {
+        let result = convert(r"p Hello \{{ world }}");
+        assert!(result.html().contains("Hello {{ world }}"));
+        assert!(!result.html().contains("<script>"));
+    }
+
+    #[test]
+    fn angular_interpolation_resolves_locator_queries() {
+        let pug = "p {{ name }}";
+        let result = convert(pug);
+        let pug_byte = pug.find("name").unwrap();
+        let html_byte = result.html_offset_for_pug(pug_byte).unwrap();
+        assert!(result.html()[html_byte..].starts_with("name"));
+    }
+
+    #[test]
+    fn supports_class_and_id_shorthand() {
+        let result = convert(r#"div.card#main(role="x")"#);
+        assert!(result.html().starts_with(r#"<div class="card" id="main" role="x">"#));
+    }
+
+    #[test]
+    fn merges_multiple_class_segments_into_one_attribute() {
+        let result = convert("div.foo.bar");
+        assert!(result.html().contains(r#"class="foo bar""#));
+    }
+
+    #[test]
+    fn merges_class_shorthand_with_an_explicit_class_attribute() {
+        let result = convert(r#"div.foo(class="bar")"#);
+        assert_eq!(result.html().matches("class=").count(), 1);
+        assert!(result.html().contains(r#"class="foo bar""#));
+    }
+
+    #[test]
+    fn merges_class_shorthand_with_a_dynamic_class_attribute() {
+        let result = convert("div.foo(class=expr)");
+        assert_eq!(result.html().matches("class=").count(), 1);
+        assert!(result.html().contains("class='foo ' + (expr)"));
+    }
+
+    #[test]
+    fn surfaces_mixin_attributes_spread_as_a_scripted_probe() {
+        let result = convert("div&attributes(obj)");
+        assert!(result.html().contains("<script>return obj;</script>"));
+    }
+
+    #[test]
+    fn emits_loop_expression_and_recurses_into_each_body() {
+        let result = convert("each item in items\n  li= item");
+        assert!(result.html().contains("<script>return items;</script>"));
+        assert!(result.html().contains("<li>"));
+    }
+
+    #[test]
+    fn handles_two_variable_each_loops() {
+        let result = convert("each value, key in items\n  li= value");
+        assert!(result.html().contains("<script>return items;</script>"));
+        assert!(result.html().contains("<li>"));
+    }
+
+    #[test]
+    fn emits_case_selector_and_recurses_into_each_branch() {
+        let result = convert("case value\n  when 1\n    p one\n  default\n    p other");
+        assert!(result.html().contains("<script>return value;</script>"));
+        assert!(result.html().contains("<p>one</p>"));
+        assert!(result.html().contains("<p>other</p>"));
+    }
+
+    #[test]
+    fn emits_buffered_comments_as_html_comments() {
+        let result = convert("// hello world");
+        assert!(result.html().contains("<!--hello world-->"));
+    }
+
+    #[test]
+    fn drops_unbuffered_comments_entirely() {
+        let result = convert("//- hidden");
+        assert!(!result.html().contains("hidden"));
+        assert!(!result.html().contains("<!--"));
+    }
+
+    #[test]
+    fn escapes_dashes_in_buffered_comments_so_they_cannot_close_early() {
+        let result = convert("// can't close this -->early");
+        assert_eq!(result.html().matches("-->").count(), 1);
+        assert!(result.html().ends_with("-->"));
+    }
+
+    #[test]
+    fn buffered_comment_resolves_locator_queries() {
+        let pug = "// hello world";
+        let result = convert(pug);
+        let pug_byte = pug.find("hello").unwrap();
+        let html_byte = result.html_offset_for_pug(pug_byte).unwrap();
+        assert!(result.html()[html_byte..].starts_with("hello"));
+    }
+
+    #[test]
+    fn reparse_reconverts_after_an_edit() {
+        let old_src = "p hello";
+        let new_src = "p hello world";
+        let mut locator = Locator::new(old_src);
+        assert!(locator.result().html().contains("hello"));
+
+        let edit = InputEdit {
+            start_byte: old_src.len(),
+            old_end_byte: old_src.len(),
+            new_end_byte: new_src.len(),
+            start_position: tree_sitter::Point { row: 0, column: old_src.len() },
+            old_end_position: tree_sitter::Point { row: 0, column: old_src.len() },
+            new_end_position: tree_sitter::Point { row: 0, column: new_src.len() },
+        };
+
+        let result = locator.reparse(edit, new_src);
+        assert!(result.html().contains("hello world"));
+    }
+
+    #[test]
+    fn reparse_reuses_unedited_siblings_without_losing_their_tag_brackets() {
+        let old_src = "div one\ndiv two";
+        let new_src = "div one!\ndiv two";
+        let mut locator = Locator::new(old_src);
+        assert!(locator.result().html().contains("<div>two</div>"));
+
+        let edit = InputEdit {
+            start_byte: 7,
+            old_end_byte: 7,
+            new_end_byte: 8,
+            start_position: tree_sitter::Point { row: 0, column: 7 },
+            old_end_position: tree_sitter::Point { row: 0, column: 7 },
+            new_end_position: tree_sitter::Point { row: 0, column: 8 },
+        };
+
+        let result = locator.reparse(edit, new_src);
+        assert!(result.html().contains("<div>one!</div>"));
+        assert!(result.html().contains("<div>two</div>"));
+    }
+
+    #[test]
+    fn queries_outside_the_document_return_none() {
+        let result = convert("p hello");
+        assert_eq!(result.html_offset_for_pug(10_000), None);
+        assert_eq!(result.pug_offset_for_html(10_000), None);
+    }
+}